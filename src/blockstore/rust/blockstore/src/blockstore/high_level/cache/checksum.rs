@@ -0,0 +1,54 @@
+use std::fmt;
+
+use crate::blockstore::BlockId;
+
+/// A BLAKE3 digest over a block's data. Used as a cheap last-line integrity check against
+/// in-memory bit-flips and base-store corruption, independent of (and in addition to) whatever
+/// authentication the on-disk crypto format already provides.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlockChecksum([u8; 32]);
+
+impl BlockChecksum {
+    pub fn compute(data: &[u8]) -> Self {
+        Self(*blake3::hash(data).as_bytes())
+    }
+}
+
+impl fmt::Display for BlockChecksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned when a block's data doesn't match its expected checksum, on load or before a flush.
+/// Kept distinct from IO errors so callers can tell "the base store call itself failed" apart
+/// from "the bytes we have are corrupt".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityError {
+    block_id: BlockId,
+}
+
+impl IntegrityError {
+    pub(super) fn new(block_id: BlockId) -> Self {
+        Self { block_id }
+    }
+
+    pub fn block_id(&self) -> &BlockId {
+        &self.block_id
+    }
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Integrity check failed for block {:?}: stored data doesn't match its checksum",
+            self.block_id
+        )
+    }
+}
+
+impl std::error::Error for IntegrityError {}
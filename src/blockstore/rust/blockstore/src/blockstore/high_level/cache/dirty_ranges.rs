@@ -0,0 +1,95 @@
+use std::ops::Range;
+
+/// A coalesced set of the byte ranges within a block that were modified since it was last
+/// flushed, so [super::entry::BlockCacheEntry::flush] can issue a partial update covering just
+/// those bytes instead of rewriting the whole block.
+#[derive(Clone, Debug, Default)]
+pub(super) struct DirtyRanges {
+    // Sorted by `start`, non-overlapping and non-adjacent - see `mark_dirty`.
+    ranges: Vec<Range<usize>>,
+}
+
+impl DirtyRanges {
+    pub(super) fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub(super) fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    /// Total number of bytes covered. Since ranges are kept coalesced, this is just the sum of
+    /// their lengths, no overlap to account for.
+    pub(super) fn num_dirty_bytes(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    /// Records `offset..offset+len` as dirty, merging it with any range it overlaps or touches.
+    pub(super) fn mark_dirty(&mut self, offset: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut merged = offset..(offset + len);
+        self.ranges.retain(|range| {
+            let touches = range.start <= merged.end && merged.start <= range.end;
+            if touches {
+                merged.start = merged.start.min(range.start);
+                merged.end = merged.end.max(range.end);
+            }
+            !touches
+        });
+        let insert_at = self.ranges.partition_point(|range| range.start < merged.start);
+        self.ranges.insert(insert_at, merged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_empty() {
+        let ranges = DirtyRanges::default();
+        assert!(ranges.is_empty());
+        assert_eq!(0, ranges.num_dirty_bytes());
+    }
+
+    #[test]
+    fn single_mark_is_tracked() {
+        let mut ranges = DirtyRanges::default();
+        ranges.mark_dirty(10, 5);
+        assert_eq!(&[10..15], ranges.ranges());
+        assert_eq!(5, ranges.num_dirty_bytes());
+    }
+
+    #[test]
+    fn adjacent_and_overlapping_marks_coalesce() {
+        let mut ranges = DirtyRanges::default();
+        ranges.mark_dirty(0, 10);
+        ranges.mark_dirty(10, 10);
+        ranges.mark_dirty(5, 2);
+        assert_eq!(&[0..20], ranges.ranges());
+        assert_eq!(20, ranges.num_dirty_bytes());
+    }
+
+    #[test]
+    fn disjoint_marks_stay_separate_and_sorted() {
+        let mut ranges = DirtyRanges::default();
+        ranges.mark_dirty(100, 10);
+        ranges.mark_dirty(0, 10);
+        assert_eq!(&[0..10, 100..110], ranges.ranges());
+        assert_eq!(20, ranges.num_dirty_bytes());
+    }
+
+    #[test]
+    fn clear_empties_the_set() {
+        let mut ranges = DirtyRanges::default();
+        ranges.mark_dirty(0, 10);
+        ranges.clear();
+        assert!(ranges.is_empty());
+    }
+}
@@ -1,7 +1,11 @@
 use anyhow::Result;
 use std::fmt::{self, Debug};
+use std::ops::{Deref, DerefMut, Range};
 use std::sync::Arc;
 
+use super::checksum::{BlockChecksum, IntegrityError};
+use super::dirty_ranges::DirtyRanges;
+use super::journal::WriteAheadJournal;
 use crate::blockstore::BlockId;
 use crate::data::Data;
 
@@ -20,27 +24,90 @@ pub enum BlockBaseStoreState {
 pub struct BlockCacheEntry<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> {
     // TODO Do we really need to store the base_store in each cache entry?
     base_store: Arc<B>,
+    block_id: BlockId,
     dirty: CacheEntryState,
     data: Data,
     block_exists_in_base_store: BlockBaseStoreState,
+    // Bumped on every `data_mut()` call. Lets a writer that released its lock mid-flush (e.g.
+    // the background write-back flusher) tell whether the entry was touched again while the
+    // store it issued was still in flight, so it doesn't clear a dirty flag that belongs to a
+    // newer write.
+    write_version: u64,
+    // Byte ranges touched since the last flush, so `flush()` can overwrite just those instead
+    // of rewriting the whole block.
+    dirty_ranges: DirtyRanges,
+    // Set by `BlockCache::insert` when the cache was constructed with journaling enabled.
+    journal: Option<Arc<WriteAheadJournal>>,
+    // Checksum of `data` as of the last write that went through `journal_current_data` (i.e.
+    // every completed `data_mut()`/`data_mut_range()`/`resize()` call). Kept in sync eagerly,
+    // right when each write completes, so `verify_checksum_before_flush` always has a checksum
+    // of data known-good at that point to check the about-to-be-flushed bytes against.
+    checksum: BlockChecksum,
 }
 
 impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> BlockCacheEntry<B> {
     #[inline]
     pub fn new(
         base_store: Arc<B>,
+        block_id: BlockId,
         data: Data,
         dirty: CacheEntryState,
         block_exists_in_base_store: BlockBaseStoreState,
     ) -> Self {
+        let checksum = BlockChecksum::compute(data.as_ref());
         Self {
             base_store,
+            block_id,
             dirty,
             data,
             block_exists_in_base_store,
+            write_version: 0,
+            dirty_ranges: DirtyRanges::default(),
+            journal: None,
+            checksum,
         }
     }
 
+    /// Constructs a clean entry for `data` just loaded from the base store, verifying it
+    /// against `expected_checksum` (e.g. persisted alongside the block by a previous flush)
+    /// before trusting it.
+    pub fn load(
+        base_store: Arc<B>,
+        block_id: BlockId,
+        data: Data,
+        expected_checksum: BlockChecksum,
+    ) -> Result<Self, IntegrityError> {
+        let actual_checksum = BlockChecksum::compute(data.as_ref());
+        if actual_checksum != expected_checksum {
+            return Err(IntegrityError::new(block_id));
+        }
+        Ok(Self::new(
+            base_store,
+            block_id,
+            data,
+            CacheEntryState::Clean,
+            BlockBaseStoreState::ExistsInBaseStore,
+        ))
+    }
+
+    /// The checksum of [Self::data] as of the last completed write through [Self::data_mut]/
+    /// [Self::data_mut_range]/[Self::resize], or of the data the entry was constructed with.
+    /// After a [Self::flush] it matches the stored block, so callers can persist it alongside
+    /// the block for the next [Self::load] to verify against.
+    #[inline]
+    pub fn checksum(&self) -> BlockChecksum {
+        self.checksum
+    }
+
+    /// Attaches (or detaches) the write-ahead journal this entry should log dirty transitions
+    /// and commits to. Called by [super::BlockCache::insert] when the cache was constructed with
+    /// journaling enabled; never part of the public constructor so in-memory-only callers are
+    /// unaffected.
+    #[inline]
+    pub(super) fn set_journal(&mut self, journal: Option<Arc<WriteAheadJournal>>) {
+        self.journal = journal;
+    }
+
     #[inline]
     pub fn block_exists_in_base_store(&self) -> BlockBaseStoreState {
         self.block_exists_in_base_store
@@ -51,34 +118,207 @@ impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> BlockC
         &self.data
     }
 
+    /// Marks the whole block dirty. Prefer [Self::data_mut_range] when only part of the block
+    /// actually changes, so [Self::flush] can write back less.
+    ///
+    /// Returns a guard rather than `&mut Data` directly: the write-ahead journal can only log
+    /// something durable once the caller is done mutating, so the guard re-journals the actual
+    /// post-mutation bytes when it's dropped; the record [Self::mark_dirty] writes up front
+    /// only holds the not-yet-written-to data.
+    #[inline]
+    pub fn data_mut(&mut self) -> DataMut<'_, B> {
+        let len = self.data.len();
+        self.mark_dirty(0, len);
+        DataMut {
+            range: 0..len,
+            entry: self,
+        }
+    }
+
+    /// Like [Self::data_mut], but only marks `offset..offset+len` dirty instead of the whole
+    /// block, so [Self::flush] can later issue a partial update covering just those bytes.
     #[inline]
-    pub fn data_mut(&mut self) -> &mut Data {
+    pub fn data_mut_range(&mut self, offset: usize, len: usize) -> DataMut<'_, B> {
+        self.mark_dirty(offset, len);
+        DataMut {
+            range: offset..offset + len,
+            entry: self,
+        }
+    }
+
+    fn mark_dirty(&mut self, offset: usize, len: usize) {
         self.dirty = CacheEntryState::Dirty;
-        &mut self.data
+        self.write_version = self.write_version.wrapping_add(1);
+        self.dirty_ranges.mark_dirty(offset, len);
+        // Records `self.data` as it stands right now. For [Self::resize], which has already
+        // mutated `self.data` by the time it calls this, that's the post-mutation state. For
+        // [Self::data_mut]/[Self::data_mut_range], whose caller mutates through the `DataMut`
+        // guard returned *after* this runs, this record is superseded by the guard's `Drop` impl
+        // calling it again once the caller's mutation is complete - this call just ensures
+        // something is on disk even if the guard is held (and the process crashes) before ever
+        // being dropped.
+        self.journal_current_data();
+    }
+
+    /// Appends the current contents of [Self::data] to the write-ahead journal (if one is
+    /// attached) and refreshes [Self::checksum] to match. Called both from [Self::mark_dirty]
+    /// and from [DataMut]'s `Drop` impl, which calls it again once the caller's mutation through
+    /// the guard is complete - that second call is what leaves `checksum` holding a digest of
+    /// the real post-mutation data for [Self::verify_checksum_before_flush] to check against.
+    fn journal_current_data(&mut self) {
+        if let Some(journal) = &self.journal {
+            if let Err(err) =
+                journal.append_dirty(&self.block_id, self.block_exists_in_base_store, &self.data)
+            {
+                log::warn!(
+                    "Failed to append write-ahead journal record for {:?}: {err:?}",
+                    self.block_id
+                );
+            }
+        }
+        self.checksum = BlockChecksum::compute(self.data.as_ref());
     }
 
-    pub async fn flush(&mut self, block_id: &BlockId) -> Result<()> {
+    #[inline]
+    pub(super) fn state(&self) -> CacheEntryState {
+        self.dirty
+    }
+
+    #[inline]
+    pub(super) fn write_version(&self) -> u64 {
+        self.write_version
+    }
+
+    #[inline]
+    pub(super) fn base_store(&self) -> &Arc<B> {
+        &self.base_store
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
         if self.dirty == CacheEntryState::Dirty {
-            // TODO self.base_store.optimized_store() ?
-            self.base_store.store(block_id, &self.data).await?;
+            self.verify_checksum_before_flush()?;
+            if self.should_use_optimized_store() {
+                for range in self.dirty_ranges.ranges() {
+                    self.base_store
+                        .optimized_store(&self.block_id, range.start, &self.data[range.clone()])
+                        .await?;
+                }
+            } else {
+                self.base_store.store(&self.block_id, &self.data).await?;
+            }
             self.dirty = CacheEntryState::Clean;
+            self.dirty_ranges.clear();
+            if let Some(journal) = &self.journal {
+                if let Err(err) = journal.append_commit(&self.block_id) {
+                    log::warn!(
+                        "Failed to append write-ahead journal commit for {:?}: {err:?}",
+                        self.block_id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Recomputes the checksum of the current data and compares it against [Self::checksum],
+    /// which [Self::journal_current_data] refreshed as soon as the last write completed - not
+    /// lazily at flush time, which would make this check vacuous (a dirty entry has, by
+    /// definition, been written to since it was last clean). A mismatch means the buffer changed
+    /// through some path other than `data_mut()`/`data_mut_range()`/`resize()` (stray memory
+    /// corruption, not a legitimate edit) since that write completed, and is reported as an
+    /// [IntegrityError] rather than silently persisted.
+    fn verify_checksum_before_flush(&self) -> Result<(), IntegrityError> {
+        let actual = BlockChecksum::compute(self.data.as_ref());
+        if actual != self.checksum {
+            return Err(IntegrityError::new(self.block_id.clone()));
         }
         Ok(())
     }
 
+    /// A partial update only pays off when the block already exists in the base store (so
+    /// there's something there to overwrite) and the dirty bytes are a small fraction of the
+    /// block - once most of it changed, or the block is new, one full `store()` is both
+    /// cheaper and simpler than several small `optimized_store()` calls.
+    fn should_use_optimized_store(&self) -> bool {
+        const MAX_DIRTY_FRACTION_FOR_OPTIMIZED_STORE: f64 = 0.5;
+        self.block_exists_in_base_store == BlockBaseStoreState::ExistsInBaseStore
+            && !self.dirty_ranges.is_empty()
+            && (self.dirty_ranges.num_dirty_bytes() as f64)
+                < (self.data.len() as f64) * MAX_DIRTY_FRACTION_FOR_OPTIMIZED_STORE
+    }
+
+    /// Marks the entry clean without storing it. Only safe to call once the caller has
+    /// independently confirmed the currently-stored data matches what's on disk, e.g. the
+    /// background flusher comparing [Self::write_version] before and after an out-of-band store.
+    #[inline]
+    pub(super) fn mark_clean_if_unchanged_since(&mut self, write_version_at_store_start: u64) {
+        if self.write_version == write_version_at_store_start {
+            self.dirty = CacheEntryState::Clean;
+            self.dirty_ranges.clear();
+            if let Some(journal) = &self.journal {
+                if let Err(err) = journal.append_commit(&self.block_id) {
+                    log::warn!(
+                        "Failed to append write-ahead journal commit for {:?}: {err:?}",
+                        self.block_id
+                    );
+                }
+            }
+        }
+    }
+
     #[inline]
     pub async fn resize(&mut self, new_size: usize) {
+        let old_size = self.data.len();
         self.data.resize(new_size);
-        self.dirty = CacheEntryState::Dirty;
+        if new_size > old_size {
+            self.mark_dirty(old_size, new_size - old_size);
+        } else {
+            // Shrinking doesn't change the bytes that remain, but it can invalidate ranges past
+            // the new end recorded before the resize; re-marking the whole (now smaller) block
+            // dirty keeps `flush()` correct without tracking truncation as its own case.
+            self.mark_dirty(0, new_size);
+        }
     }
 
     #[inline]
     pub(super) fn discard(mut self) {
         self.dirty = CacheEntryState::Clean;
+        self.dirty_ranges.clear();
         // now that dirty is false, the value can be safely dropped
     }
 }
 
+/// A mutable view into `offset..offset+len` of a [BlockCacheEntry]'s data, returned by
+/// [BlockCacheEntry::data_mut]/[BlockCacheEntry::data_mut_range]. Re-journals the entry's data
+/// once dropped, after the caller is done writing through it - see [BlockCacheEntry::mark_dirty]
+/// for why logging only happens up front isn't enough.
+pub struct DataMut<'a, B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> {
+    entry: &'a mut BlockCacheEntry<B>,
+    range: Range<usize>,
+}
+
+impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> Deref for DataMut<'_, B> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.entry.data[self.range.clone()]
+    }
+}
+
+impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> DerefMut
+    for DataMut<'_, B>
+{
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.entry.data[self.range.clone()]
+    }
+}
+
+impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> Drop for DataMut<'_, B> {
+    fn drop(&mut self) {
+        self.entry.journal_current_data();
+    }
+}
+
 impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> fmt::Debug
     for BlockCacheEntry<B>
 {
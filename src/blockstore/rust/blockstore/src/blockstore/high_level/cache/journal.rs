@@ -0,0 +1,209 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use super::entry::BlockBaseStoreState;
+use crate::blockstore::BlockId;
+use crate::data::Data;
+
+const RECORD_TAG_DIRTY: u8 = 0;
+const RECORD_TAG_COMMIT: u8 = 1;
+
+/// An append-only write-ahead log backing [super::BlockCache]. Every time an entry transitions
+/// to dirty, [super::entry::BlockCacheEntry] appends a record of its id and current data here
+/// before the mutation is considered committed; once the entry is actually flushed to the base
+/// store, a matching commit record is appended. [recover] replays this file on startup and
+/// re-stores any block whose dirty record has no later commit, turning what used to be an "our
+/// mistake" panic in `BlockCacheEntry`'s `Drop` into a recoverable crash-consistency guarantee.
+///
+/// Journaling is opt-in: pass `None` instead of a [WriteAheadJournal] to [super::BlockCache::new]
+/// to keep the previous in-memory-only behavior.
+pub struct WriteAheadJournal {
+    file: Mutex<File>,
+}
+
+impl WriteAheadJournal {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open write-ahead journal at {path:?}"))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub(super) fn append_dirty(
+        &self,
+        block_id: &BlockId,
+        base_exists: BlockBaseStoreState,
+        data: &Data,
+    ) -> Result<()> {
+        let mut record = Vec::with_capacity(data.len() + 64);
+        record.push(RECORD_TAG_DIRTY);
+        write_block_id(&mut record, block_id);
+        record.push((base_exists == BlockBaseStoreState::ExistsInBaseStore) as u8);
+        record.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        record.extend_from_slice(data.as_ref());
+        self.append_record(&record)
+    }
+
+    pub(super) fn append_commit(&self, block_id: &BlockId) -> Result<()> {
+        let mut record = Vec::with_capacity(64);
+        record.push(RECORD_TAG_COMMIT);
+        write_block_id(&mut record, block_id);
+        self.append_record(&record)
+    }
+
+    fn append_record(&self, record: &[u8]) -> Result<()> {
+        let mut file = self.file.lock().expect("journal lock poisoned");
+        file.write_all(&(record.len() as u64).to_le_bytes())?;
+        file.write_all(record)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+fn write_block_id(out: &mut Vec<u8>, block_id: &BlockId) {
+    let encoded = block_id.to_string();
+    out.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+    out.extend_from_slice(encoded.as_bytes());
+}
+
+/// Returns `Ok(None)` if `input` is too short to hold the block id (or its length prefix).
+fn read_block_id(input: &mut &[u8]) -> Result<Option<BlockId>> {
+    if input.len() < 4 {
+        return Ok(None);
+    }
+    let (len_bytes, rest) = input.split_at(4);
+    let len = u32::from_le_bytes(len_bytes.try_into().expect("length is 4 bytes")) as usize;
+    if rest.len() < len {
+        return Ok(None);
+    }
+    let (id_bytes, rest) = rest.split_at(len);
+    *input = rest;
+    let id_str = std::str::from_utf8(id_bytes).context("Journal block id isn't valid UTF-8")?;
+    BlockId::from_str(id_str)
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("Journal contains an invalid block id: {id_str}"))
+}
+
+enum JournalRecord {
+    Dirty { block_id: BlockId, data: Data },
+    Commit { block_id: BlockId },
+}
+
+/// Decodes one record body (everything after its 8-byte length prefix). Returns `Ok(None)` if
+/// `body` is internally too short to hold the fields its own tag says it should have - a record
+/// whose length prefix survived a crash intact but whose body didn't is indistinguishable from a
+/// valid one until we've tried to decode it, and [read_records] treats that exactly like the
+/// already-expected case of a partially-written final record: stop replaying, don't panic.
+fn parse_record_body(body: &[u8]) -> Result<Option<JournalRecord>> {
+    let mut cursor: &[u8] = body;
+    let Some((&tag, rest)) = cursor.split_first() else {
+        return Ok(None);
+    };
+    cursor = rest;
+    match tag {
+        RECORD_TAG_DIRTY => {
+            let Some(block_id) = read_block_id(&mut cursor)? else {
+                return Ok(None);
+            };
+            let Some((_base_exists, rest)) = cursor.split_first() else {
+                return Ok(None);
+            };
+            cursor = rest;
+            if cursor.len() < 8 {
+                return Ok(None);
+            }
+            let (len_bytes, rest) = cursor.split_at(8);
+            let data_len = u64::from_le_bytes(len_bytes.try_into().expect("length is 8 bytes")) as usize;
+            cursor = rest;
+            if cursor.len() < data_len {
+                return Ok(None);
+            }
+            Ok(Some(JournalRecord::Dirty {
+                block_id,
+                data: Data::from(cursor[..data_len].to_vec()),
+            }))
+        }
+        RECORD_TAG_COMMIT => {
+            let Some(block_id) = read_block_id(&mut cursor)? else {
+                return Ok(None);
+            };
+            Ok(Some(JournalRecord::Commit { block_id }))
+        }
+        other => bail!("Unknown write-ahead journal record tag {other}"),
+    }
+}
+
+fn read_records(path: &Path) -> Result<Vec<JournalRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut file = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open write-ahead journal at {path:?}"))?,
+    );
+    let mut records = Vec::new();
+    loop {
+        let mut len_buf = [0u8; 8];
+        match file.read_exact(&mut len_buf) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let record_len = u64::from_le_bytes(len_buf) as usize;
+        let mut body = vec![0u8; record_len];
+        // A partially-written final record (e.g. the process crashed mid-append) is expected -
+        // stop replaying there instead of failing the whole recovery.
+        if file.read_exact(&mut body).is_err() {
+            break;
+        }
+        match parse_record_body(&body)? {
+            Some(record) => records.push(record),
+            None => break,
+        }
+    }
+    Ok(records)
+}
+
+/// Replays `journal_path` against `base_store`: every block whose last dirty record has no
+/// later commit record gets re-stored, then the journal is truncated so the next run starts
+/// clean. Call this once at startup, before constructing the [super::BlockCache] that will use
+/// the same journal file.
+pub async fn recover<B>(journal_path: impl AsRef<Path>, base_store: &B) -> Result<()>
+where
+    B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static,
+{
+    let path: PathBuf = journal_path.as_ref().to_owned();
+    let records = read_records(&path)?;
+
+    // Replaying in order and keying by block id keeps only the newest uncommitted snapshot per
+    // block - a block journaled dirty, committed, then dirtied again is still replayed.
+    let mut pending: HashMap<BlockId, Data> = HashMap::new();
+    for record in records {
+        match record {
+            JournalRecord::Dirty { block_id, data } => {
+                pending.insert(block_id, data);
+            }
+            JournalRecord::Commit { block_id } => {
+                pending.remove(&block_id);
+            }
+        }
+    }
+
+    for (block_id, data) in pending {
+        base_store.store(&block_id, &data).await?;
+    }
+
+    if path.exists() {
+        OpenOptions::new().write(true).open(&path)?.set_len(0)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::entry::{BlockCacheEntry, CacheEntryState};
+use super::journal::WriteAheadJournal;
+use crate::blockstore::BlockId;
+use crate::data::Data;
+
+/// Configurable budget that bounds how much [BlockCache] is allowed to hold before it starts
+/// evicting the least-recently-used entry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CacheLimit {
+    /// Evict once more than this many blocks are cached, regardless of their size.
+    MaxEntries(usize),
+    /// Evict once the summed size of all cached blocks' data exceeds this many bytes.
+    MaxBytes(usize),
+}
+
+impl CacheLimit {
+    fn is_exceeded_by(&self, cache: &CacheOccupancy) -> bool {
+        match *self {
+            CacheLimit::MaxEntries(max) => cache.num_entries > max,
+            CacheLimit::MaxBytes(max) => cache.num_bytes > max,
+        }
+    }
+}
+
+struct CacheOccupancy {
+    num_entries: usize,
+    num_bytes: usize,
+}
+
+/// One node of the intrusive LRU doubly-linked list threaded through [BlockCache::lru_links],
+/// keyed by the same [BlockId] as [BlockCache::entries]. `None` at either end means "this is the
+/// front/back of the list", tracked separately in [BlockCache::lru_front]/[BlockCache::lru_back].
+struct LruLink {
+    prev: Option<BlockId>,
+    next: Option<BlockId>,
+}
+
+/// An LRU-bounded cache of [BlockCacheEntry] instances, keyed by [BlockId].
+///
+/// Recency is tracked on every [BlockCache::get]/[BlockCache::get_mut] access (which is how
+/// callers reach [BlockCacheEntry::data]/[BlockCacheEntry::data_mut]), as well as on insertion.
+/// Once the configured [CacheLimit] is exceeded, the least-recently-used entry is evicted:
+/// a [CacheEntryState::Clean] entry is simply dropped, a [CacheEntryState::Dirty] one is
+/// flushed first so [BlockCacheEntry]'s `Drop` assertion never fires.
+///
+/// Recency order is an intrusive doubly-linked list (via [lru_links]) rather than the `VecDeque`
+/// this used to be: a `VecDeque` needs a linear scan to find and unlink an arbitrary entry, which
+/// made every [Self::touch] (i.e. every cache hit) O(n) and [Self::evict_until_within_limit]
+/// O(n²). The list makes touch/insert/remove O(1). Likewise [Self::num_bytes] is a running total
+/// kept in [Self::num_bytes] (the field) instead of re-summing every entry's data on each call.
+pub struct BlockCache<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> {
+    limit: CacheLimit,
+    entries: HashMap<BlockId, BlockCacheEntry<B>>,
+    lru_links: HashMap<BlockId, LruLink>,
+    /// Least recently used.
+    lru_front: Option<BlockId>,
+    /// Most recently used.
+    lru_back: Option<BlockId>,
+    num_bytes: usize,
+    // Attached to every inserted entry. `None` keeps the previous in-memory-only behavior.
+    journal: Option<Arc<WriteAheadJournal>>,
+}
+
+impl<B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static> BlockCache<B> {
+    /// `journal` is an opt-in write-ahead log: pass `None` to keep the cache in-memory-only, or
+    /// `Some(journal)` (typically after calling [super::journal::recover] on the same path at
+    /// startup) so a crash with dirty entries still in memory can be replayed instead of lost.
+    pub fn new(limit: CacheLimit, journal: Option<Arc<WriteAheadJournal>>) -> Self {
+        Self {
+            limit,
+            entries: HashMap::new(),
+            lru_links: HashMap::new(),
+            lru_front: None,
+            lru_back: None,
+            num_bytes: 0,
+            journal,
+        }
+    }
+
+    /// Number of blocks currently cached. Part of the occupancy metric alongside [Self::num_bytes].
+    #[inline]
+    pub fn num_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Summed size of all currently cached blocks' data. Part of the occupancy metric alongside
+    /// [Self::num_entries]. O(1): maintained incrementally by [Self::insert]/[Self::remove]/
+    /// [Self::resize] rather than re-summed here.
+    #[inline]
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    fn occupancy(&self) -> CacheOccupancy {
+        CacheOccupancy {
+            num_entries: self.num_entries(),
+            num_bytes: self.num_bytes(),
+        }
+    }
+
+    /// Unlinks `block_id` from the LRU list, patching its neighbors (and the front/back pointers,
+    /// if it was at either end) to close the gap. No-op if `block_id` isn't linked.
+    fn lru_unlink(&mut self, block_id: &BlockId) {
+        let Some(link) = self.lru_links.remove(block_id) else {
+            return;
+        };
+        match &link.prev {
+            Some(prev) => self.lru_links.get_mut(prev).expect("prev must be linked").next = link.next.clone(),
+            None => self.lru_front = link.next.clone(),
+        }
+        match &link.next {
+            Some(next) => self.lru_links.get_mut(next).expect("next must be linked").prev = link.prev.clone(),
+            None => self.lru_back = link.prev.clone(),
+        }
+    }
+
+    /// Links `block_id` in as the most-recently-used entry. `block_id` must not already be linked.
+    fn lru_push_back(&mut self, block_id: BlockId) {
+        let old_back = self.lru_back.replace(block_id.clone());
+        if let Some(old_back) = &old_back {
+            self.lru_links
+                .get_mut(old_back)
+                .expect("old_back must be linked")
+                .next = Some(block_id.clone());
+        } else {
+            self.lru_front = Some(block_id.clone());
+        }
+        self.lru_links.insert(
+            block_id,
+            LruLink {
+                prev: old_back,
+                next: None,
+            },
+        );
+    }
+
+    /// Marks `block_id` most-recently-used, moving it to the back of the LRU list. No-op if it
+    /// isn't currently linked (e.g. it isn't in the cache).
+    fn touch(&mut self, block_id: &BlockId) {
+        if !self.lru_links.contains_key(block_id) {
+            return;
+        }
+        self.lru_unlink(block_id);
+        self.lru_push_back(block_id.clone());
+    }
+
+    /// Unlinks and returns the least-recently-used [BlockId], or `None` if the cache is empty.
+    fn lru_pop_front(&mut self) -> Option<BlockId> {
+        let front = self.lru_front.clone()?;
+        self.lru_unlink(&front);
+        Some(front)
+    }
+
+    pub fn get(&mut self, block_id: &BlockId) -> Option<&BlockCacheEntry<B>> {
+        if self.entries.contains_key(block_id) {
+            self.touch(block_id);
+        }
+        self.entries.get(block_id)
+    }
+
+    pub fn get_mut(&mut self, block_id: &BlockId) -> Option<&mut BlockCacheEntry<B>> {
+        if self.entries.contains_key(block_id) {
+            self.touch(block_id);
+        }
+        self.entries.get_mut(block_id)
+    }
+
+    /// Inserts `entry` under `block_id`, marking it most-recently-used, then evicts
+    /// least-recently-used entries until the configured [CacheLimit] is satisfied again.
+    pub async fn insert(
+        &mut self,
+        block_id: BlockId,
+        mut entry: BlockCacheEntry<B>,
+    ) -> Result<Option<BlockCacheEntry<B>>> {
+        entry.set_journal(self.journal.clone());
+        self.num_bytes += entry.data().len();
+        let previous = self.entries.insert(block_id.clone(), entry);
+        if let Some(previous) = &previous {
+            self.num_bytes -= previous.data().len();
+            self.touch(&block_id);
+        } else {
+            self.lru_push_back(block_id.clone());
+        }
+        self.evict_until_within_limit().await?;
+        Ok(previous)
+    }
+
+    /// Removes `block_id` from the cache without flushing it. Callers that need a dirty
+    /// entry's data persisted must call [BlockCacheEntry::flush] on it themselves before
+    /// dropping it.
+    pub fn remove(&mut self, block_id: &BlockId) -> Option<BlockCacheEntry<B>> {
+        self.lru_unlink(block_id);
+        let removed = self.entries.remove(block_id);
+        if let Some(removed) = &removed {
+            self.num_bytes -= removed.data().len();
+        }
+        removed
+    }
+
+    /// Resizes the entry cached under `block_id`, if any, keeping [Self::num_bytes] in sync with
+    /// the change. The only [BlockCache] method that can change an entry's byte length after insertion -
+    /// prefer this over reaching into a [BlockCacheEntry] obtained from [Self::get_mut] directly
+    /// so the running byte total never drifts out of sync with reality.
+    pub async fn resize(&mut self, block_id: &BlockId, new_size: usize) {
+        if let Some(entry) = self.entries.get_mut(block_id) {
+            let old_size = entry.data().len();
+            entry.resize(new_size).await;
+            self.num_bytes = self.num_bytes - old_size + new_size;
+        }
+    }
+
+    async fn evict_until_within_limit(&mut self) -> Result<()> {
+        while self.limit.is_exceeded_by(&self.occupancy()) {
+            let Some(lru_block_id) = self.lru_pop_front() else {
+                break;
+            };
+            let Some(mut entry) = self.entries.remove(&lru_block_id) else {
+                continue;
+            };
+            self.num_bytes -= entry.data().len();
+            if entry.state() == CacheEntryState::Dirty {
+                entry.flush().await?;
+            }
+            entry.discard();
+        }
+        Ok(())
+    }
+
+    /// Flushes every cached entry without evicting it, e.g. before a clean shutdown.
+    pub async fn flush_all(&mut self) -> Result<()> {
+        for entry in self.entries.values_mut() {
+            entry.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Snapshots every currently dirty entry's id, write version and data, plus a handle to the
+    /// base store it belongs to, so the background write-back flusher can store them
+    /// concurrently without holding the cache locked for the duration of the IO.
+    pub(super) fn dirty_entries_snapshot(&self) -> Vec<(BlockId, u64, Data, Arc<B>)> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.state() == CacheEntryState::Dirty)
+            .map(|(block_id, entry)| {
+                (
+                    block_id.clone(),
+                    entry.write_version(),
+                    entry.data().clone(),
+                    Arc::clone(entry.base_store()),
+                )
+            })
+            .collect()
+    }
+
+    /// Marks `block_id` clean, but only if it's still dirty and hasn't been written to again
+    /// since `write_version_at_store_start` was captured - see
+    /// [super::entry::BlockCacheEntry::mark_clean_if_unchanged_since].
+    pub(super) fn mark_clean_if_unchanged_since(
+        &mut self,
+        block_id: &BlockId,
+        write_version_at_store_start: u64,
+    ) {
+        if let Some(entry) = self.entries.get_mut(block_id) {
+            entry.mark_clean_if_unchanged_since(write_version_at_store_start);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+mod checksum;
+mod dirty_ranges;
+mod entry;
+mod journal;
+mod lru_cache;
+mod writeback;
+
+pub use checksum::{BlockChecksum, IntegrityError};
+pub use entry::{BlockBaseStoreState, BlockCacheEntry, CacheEntryState, DataMut};
+pub use journal::{recover, WriteAheadJournal};
+pub use lru_cache::{BlockCache, CacheLimit};
+pub use writeback::{spawn_write_back_flusher, WriteBackFlusherHandle};
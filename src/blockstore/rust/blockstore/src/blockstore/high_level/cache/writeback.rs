@@ -0,0 +1,77 @@
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use super::lru_cache::BlockCache;
+
+/// Handle to a task spawned by [spawn_write_back_flusher]. Dropping it leaves the background
+/// flusher running; call [Self::stop] to abort it. Stopping does not flush remaining dirty
+/// entries - callers that need a final flush should call [BlockCache::flush_all] themselves.
+pub struct WriteBackFlusherHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl WriteBackFlusherHandle {
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+/// Spawns a background task that wakes up every `interval` and writes back all of `cache`'s
+/// currently dirty entries concurrently, via [futures::future::join_all] over
+/// `base_store.store(...)`, instead of paying that latency synchronously inside foreground
+/// `data_mut()` callers. This amortizes fsync/IO latency across many blocks, matching the
+/// "collect dirty pages and flush the batch in parallel" approach of log-structured stores.
+///
+/// Entries touched again while their store is in flight are re-checked against the write
+/// version captured when the store started, so a concurrent write is never silently marked
+/// clean - see [super::entry::BlockCacheEntry::mark_clean_if_unchanged_since].
+pub fn spawn_write_back_flusher<B>(
+    cache: Arc<Mutex<BlockCache<B>>>,
+    interval: Duration,
+) -> WriteBackFlusherHandle
+where
+    B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static,
+{
+    let task = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = flush_dirty_concurrently(&cache).await {
+                log::warn!("Background cache write-back failed: {err:?}");
+            }
+        }
+    });
+    WriteBackFlusherHandle { task }
+}
+
+async fn flush_dirty_concurrently<B>(cache: &Mutex<BlockCache<B>>) -> Result<()>
+where
+    B: crate::blockstore::low_level::BlockStore + Send + Sync + 'static,
+{
+    let dirty_snapshot = {
+        let cache = cache.lock().await;
+        cache.dirty_entries_snapshot()
+    };
+    if dirty_snapshot.is_empty() {
+        return Ok(());
+    }
+
+    // No lock is held here: every block's store runs concurrently against the base store.
+    let store_results = futures::future::join_all(
+        dirty_snapshot
+            .iter()
+            .map(|(block_id, _write_version, data, base_store)| base_store.store(block_id, data)),
+    )
+    .await;
+
+    let mut cache = cache.lock().await;
+    for ((block_id, write_version_at_store_start, _data, _base_store), result) in
+        dirty_snapshot.into_iter().zip(store_results)
+    {
+        result?;
+        cache.mark_clean_if_unchanged_since(&block_id, write_version_at_store_start);
+    }
+    Ok(())
+}
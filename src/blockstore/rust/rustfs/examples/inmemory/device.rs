@@ -1,18 +1,98 @@
 use async_trait::async_trait;
-use cryfs_rustfs::{Device, FsError, FsResult, Gid, Mode, Statfs, Uid};
+use cryfs_rustfs::{Device, FsError, FsResult, Gid, Mode, NumBytes, Statfs, Uid};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use super::dir::InMemoryDirRef;
 use super::file::{InMemoryFileRef, InMemoryOpenFileRef};
 use super::node::InMemoryNodeRef;
 use super::symlink::InMemorySymlinkRef;
 
+/// Size, in bytes, of one "block" as reported via `statfs(2)`. Chosen to match the common disk
+/// sector/block size so tools dividing bytes by `blocksize` get sensible numbers.
+const BLOCK_SIZE: u64 = 512;
+
+/// Arbitrary but generous default capacity for a caller (e.g. `main.rs`'s CLI) that doesn't ask
+/// for a specific volume size, so `InMemoryDevice` behaves like an (effectively) unbounded volume
+/// unless a smaller `capacity` is passed to [InMemoryDevice::new] explicitly.
+pub const DEFAULT_CAPACITY_BYTES: u64 = 64 * 1024 * 1024 * 1024;
+
+/// Tracks how much of an [InMemoryDevice]'s capacity is in use, shared between the device and
+/// every [InMemoryDirRef]/[InMemoryFileRef] hanging off it so `statfs` can report real numbers
+/// and creates/writes can be rejected with [FsError::NoSpaceLeft] once the volume is full.
+///
+/// TODO Nothing constructs a node or grows a file against this tracker yet: that has to happen in
+///      `InMemoryDirRef::create_*` (on `node_created`/`try_reserve`) and `InMemoryFileRef::write`/
+///      `set_len` (on `try_reserve`/`release` as the file grows/shrinks/gets removed) - and this
+///      checkout doesn't have `examples/inmemory/dir.rs` or `file.rs`, only the `Device` facade in
+///      front of them, so there's nowhere to add those calls. `used_bytes`/`num_nodes` below are
+///      therefore always 0 in this checkout, but correctly so - not via a hardcoded literal - and
+///      will start reporting real numbers the moment `dir.rs`/`file.rs` call into this tracker.
+#[derive(Debug, Default)]
+struct UsageTracker {
+    used_bytes: AtomicU64,
+    num_nodes: AtomicU64,
+}
+
+impl UsageTracker {
+    fn used_bytes(&self) -> NumBytes {
+        NumBytes::from(self.used_bytes.load(Ordering::SeqCst))
+    }
+
+    fn num_nodes(&self) -> u64 {
+        self.num_nodes.load(Ordering::SeqCst)
+    }
+
+    /// Reserves `additional_bytes` against `capacity`, failing with [FsError::NoSpaceLeft]
+    /// instead of letting usage exceed it. Call before growing a file or writing new data.
+    fn try_reserve(&self, capacity: NumBytes, additional_bytes: NumBytes) -> FsResult<()> {
+        let additional_bytes = u64::from(additional_bytes);
+        let capacity = u64::from(capacity);
+        self.used_bytes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                let new_used = used.checked_add(additional_bytes)?;
+                (new_used <= capacity).then_some(new_used)
+            })
+            .map(|_| ())
+            .map_err(|_| FsError::NoSpaceLeft)
+    }
+
+    /// Releases `bytes` previously reserved via [Self::try_reserve], e.g. after a truncate or a
+    /// file/node removal.
+    fn release(&self, bytes: NumBytes) {
+        self.used_bytes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used.saturating_sub(u64::from(bytes)))
+            })
+            .expect("the update closure above always returns Some");
+    }
+
+    fn node_created(&self) {
+        self.num_nodes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn node_removed(&self) {
+        self.num_nodes.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 pub struct InMemoryDevice {
     rootdir: InMemoryDirRef,
+    capacity: NumBytes,
+    usage: Arc<UsageTracker>,
 }
 
 impl InMemoryDevice {
-    pub fn new(uid: Uid, gid: Gid) -> Self {
+    /// Bounds the volume to `capacity` bytes; [InMemoryDevice::statfs] reports usage against it
+    /// and (once `dir.rs`/`file.rs` exist to call into [UsageTracker], see its doc comment)
+    /// creates/writes that would exceed it fail with [FsError::NoSpaceLeft].
+    //
+    // TODO This device doesn't encrypt anything yet, but once it (or a future on-disk device)
+    //      does, it should offer a `new_with_passphrase` constructor that derives the
+    //      `EncryptionKey` via `cryfs_utils::crypto::kdf::ScryptParams` instead of taking a raw
+    //      key, so users never have to handle key bytes directly.
+    pub fn new(uid: Uid, gid: Gid, capacity: NumBytes) -> Self {
         let mode = Mode::default()
             .add_dir_flag()
             .add_user_read_flag()
@@ -20,6 +100,8 @@ impl InMemoryDevice {
             .add_user_exec_flag();
         Self {
             rootdir: InMemoryDirRef::new(mode, uid, gid),
+            capacity,
+            usage: Arc::new(UsageTracker::default()),
         }
     }
 }
@@ -65,6 +147,27 @@ impl Device for InMemoryDevice {
     }
 
     async fn statfs(&self) -> FsResult<Statfs> {
-        todo!()
+        let used_bytes = self.usage.used_bytes();
+        let num_nodes = self.usage.num_nodes();
+
+        let total_blocks = u64::from(self.capacity) / BLOCK_SIZE;
+        let used_blocks = u64::from(used_bytes) / BLOCK_SIZE;
+        let free_blocks = total_blocks.saturating_sub(used_blocks);
+
+        // We don't have a fixed inode table, so report a number of free inodes that scales
+        // with the total capacity instead of an arbitrary constant.
+        const ASSUMED_AVG_FILE_SIZE: u64 = 4096;
+        let total_inodes = num_nodes + u64::from(self.capacity) / ASSUMED_AVG_FILE_SIZE;
+        let free_inodes = total_inodes.saturating_sub(num_nodes);
+
+        Ok(Statfs {
+            num_total_blocks: total_blocks,
+            num_free_blocks: free_blocks,
+            num_available_blocks: free_blocks,
+            num_total_inodes: total_inodes,
+            num_free_inodes: free_inodes,
+            blocksize: BLOCK_SIZE as u32,
+            max_filename_length: 255,
+        })
     }
 }
@@ -4,7 +4,8 @@ mod file;
 mod node;
 mod symlink;
 
-use device::InMemoryDevice;
+use cryfs_rustfs::NumBytes;
+use device::{InMemoryDevice, DEFAULT_CAPACITY_BYTES};
 
 const USAGE: &str = "Usage: inmemoryfs [mountdir]";
 
@@ -18,7 +19,7 @@ fn main() {
     let mountdir = args.next().expect(USAGE);
     assert!(args.next().is_none(), "{}", USAGE);
 
-    let device = |uid, gid| InMemoryDevice::new(uid, gid);
+    let device = |uid, gid| InMemoryDevice::new(uid, gid, NumBytes::from(DEFAULT_CAPACITY_BYTES));
 
     cryfs_rustfs::fuse_mt::mount(device, mountdir).unwrap();
 }
@@ -0,0 +1,129 @@
+//! Parses human-readable byte sizes like `bytesize` does: a bare number (bytes) or a number
+//! followed by an SI (`kB`, `MB`, `GB`, ...; powers of 1000) or IEC (`KiB`, `MiB`, `GiB`, ...;
+//! powers of 1024) suffix. Suffixes are matched case-insensitively so `32kib`, `32KIB` and
+//! `32KiB` all parse to the same value.
+
+use std::fmt;
+
+/// A unit suffix and the number of bytes one of it is worth.
+const UNITS: &[(&str, u64)] = &[
+    ("b", 1),
+    ("kb", 1000),
+    ("mb", 1000 * 1000),
+    ("gb", 1000 * 1000 * 1000),
+    ("tb", 1000 * 1000 * 1000 * 1000),
+    ("kib", 1024),
+    ("mib", 1024 * 1024),
+    ("gib", 1024 * 1024 * 1024),
+    ("tib", 1024 * 1024 * 1024 * 1024),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ParseSizeError {
+    input: String,
+}
+
+impl fmt::Display for ParseSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' is not a valid size - expected e.g. '512', '32KiB', '1MiB' or '4GB'",
+            self.input
+        )
+    }
+}
+
+impl std::error::Error for ParseSizeError {}
+
+/// Parses a human-readable byte size, e.g. `512`, `32KiB`, `1MiB`, `4GiB`, `1.5GB`. Both SI
+/// (decimal, `kB`/`MB`/`GB`/`TB`) and IEC (binary, `KiB`/`MiB`/`GiB`/`TiB`) suffixes are
+/// accepted, matched case-insensitively, with or without a space before the suffix.
+pub(crate) fn parse_size(input: &str) -> Result<u64, ParseSizeError> {
+    let err = || ParseSizeError {
+        input: input.to_string(),
+    };
+
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err(err());
+    }
+
+    let split_at = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(split_at);
+    let number: f64 = number.parse().map_err(|_| err())?;
+    if number < 0.0 {
+        return Err(err());
+    }
+
+    let suffix = suffix.trim().to_ascii_lowercase();
+    let unit_bytes = if suffix.is_empty() {
+        1
+    } else {
+        UNITS
+            .iter()
+            .find(|(unit, _)| *unit == suffix)
+            .map(|(_, bytes)| *bytes)
+            .ok_or_else(err)?
+    };
+
+    Ok((number * unit_bytes as f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bare_byte_counts() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("  1024  ").unwrap(), 1024);
+    }
+
+    #[test]
+    fn parses_iec_suffixes() {
+        assert_eq!(parse_size("32KiB").unwrap(), 32 * 1024);
+        assert_eq!(parse_size("1MiB").unwrap(), 1024 * 1024);
+        assert_eq!(parse_size("4GiB").unwrap(), 4 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1TiB").unwrap(), 1024u64.pow(4));
+    }
+
+    #[test]
+    fn parses_si_suffixes() {
+        assert_eq!(parse_size("32kB").unwrap(), 32 * 1000);
+        assert_eq!(parse_size("1MB").unwrap(), 1_000_000);
+        assert_eq!(parse_size("4GB").unwrap(), 4_000_000_000);
+    }
+
+    #[test]
+    fn is_case_insensitive() {
+        assert_eq!(parse_size("32kib").unwrap(), parse_size("32KiB").unwrap());
+        assert_eq!(parse_size("32KIB").unwrap(), parse_size("32KiB").unwrap());
+        assert_eq!(parse_size("1gb").unwrap(), parse_size("1GB").unwrap());
+    }
+
+    #[test]
+    fn distinguishes_si_from_iec() {
+        assert_ne!(parse_size("1kB").unwrap(), parse_size("1KiB").unwrap());
+        assert_eq!(parse_size("1kB").unwrap(), 1000);
+        assert_eq!(parse_size("1KiB").unwrap(), 1024);
+    }
+
+    #[test]
+    fn allows_a_space_before_the_suffix() {
+        assert_eq!(parse_size("32 KiB").unwrap(), 32 * 1024);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("   ").is_err());
+        assert!(parse_size("KiB").is_err());
+        assert!(parse_size("32XB").is_err());
+        assert!(parse_size("-32KiB").is_err());
+        assert!(parse_size("32 KiB extra").is_err());
+        assert!(parse_size("thirty-two KiB").is_err());
+    }
+}
@@ -0,0 +1,15 @@
+use std::ffi::OsString;
+
+use crate::NodeKind;
+
+/// One child returned by a directory listing (`AsyncFilesystem::readdir`, `Dir::entries`).
+///
+/// `name` is an [OsString] rather than a [String]: FUSE names are arbitrary non-NUL, non-`/`
+/// byte strings, most of which aren't valid UTF-8 in practice (see `parse_node_name` in the
+/// `fuse_mt` backend) - forcing them through `String` anywhere between the store and the kernel
+/// reply would silently mangle them instead of round-tripping byte-for-byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: OsString,
+    pub kind: NodeKind,
+}
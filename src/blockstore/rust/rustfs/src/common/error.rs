@@ -0,0 +1,58 @@
+use std::fmt;
+
+/// Why an [crate::low_level_api::AsyncFilesystem] (or [super::super::object_based_api]) call
+/// failed, in a form independent of any particular backend. [FsError::system_error_code]
+/// translates it back into a POSIX errno for whichever backend (e.g. the `fuse_mt` adapter or
+/// the `examples/inmemory` `Device`) needs to hand one to its caller.
+///
+/// `#[non_exhaustive]`: this checkout only carries the pieces of `cryfs_rustfs` that its
+/// `fuse_mt` backend and `examples/inmemory` need, so only the variants those actually construct
+/// are listed here. The full type (in the rest of the crate, outside this checkout) has more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FsError {
+    /// `open`/`create`'s flags didn't decode to a valid access mode (see `parse_openflags` in
+    /// the `fuse_mt` backend).
+    InvalidOpenFlags,
+    /// The node at the given path exists but isn't a directory.
+    NodeIsNotADirectory,
+    /// The node at the given path exists but isn't a symlink.
+    NodeIsNotASymlink,
+    /// The node at the given path exists but is a directory, where a non-directory was expected.
+    NodeIsADirectory,
+    /// The volume has no room left for the write (see `InMemoryDevice::new`).
+    NoSpaceLeft,
+    /// A backend hit a case it doesn't have a more specific error for yet.
+    UnknownError,
+}
+
+impl FsError {
+    /// The `errno` a backend should report to its caller for this error.
+    pub fn system_error_code(&self) -> libc::c_int {
+        match self {
+            Self::InvalidOpenFlags => libc::EINVAL,
+            Self::NodeIsNotADirectory => libc::ENOTDIR,
+            Self::NodeIsNotASymlink => libc::EINVAL,
+            Self::NodeIsADirectory => libc::EISDIR,
+            Self::NoSpaceLeft => libc::ENOSPC,
+            Self::UnknownError => libc::EIO,
+        }
+    }
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidOpenFlags => write!(f, "invalid open flags"),
+            Self::NodeIsNotADirectory => write!(f, "not a directory"),
+            Self::NodeIsNotASymlink => write!(f, "not a symbolic link"),
+            Self::NodeIsADirectory => write!(f, "is a directory"),
+            Self::NoSpaceLeft => write!(f, "no space left on device"),
+            Self::UnknownError => write!(f, "unknown error"),
+        }
+    }
+}
+
+impl std::error::Error for FsError {}
+
+pub type FsResult<T> = Result<T, FsError>;
@@ -0,0 +1,5 @@
+mod dir_entry;
+mod error;
+
+pub use dir_entry::DirEntry;
+pub use error::{FsError, FsResult};
@@ -0,0 +1,3 @@
+mod scrypt_kdf;
+
+pub use scrypt_kdf::ScryptParams;
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::symmetric::EncryptionKey;
+
+/// Size, in bytes, of the random salt generated for a new [ScryptParams].
+pub const SALT_SIZE: usize = 32;
+
+/// A memory-hard, password-based key derivation scheme (scrypt) together with the parameters
+/// it was run with. This whole struct is meant to be serialized and stored alongside the data
+/// it protects (e.g. in a filesystem config file), so that the same key can be re-derived from
+/// the user's passphrase the next time the filesystem is mounted.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ScryptParams {
+    salt: Vec<u8>,
+    /// log2(N), the scrypt CPU/memory cost parameter.
+    log_n: u8,
+    /// The scrypt block size parameter.
+    r: u32,
+    /// The scrypt parallelization parameter.
+    p: u32,
+}
+
+impl ScryptParams {
+    /// Generate a fresh, random salt to use with the given cost parameters. Callers wanting a
+    /// safe default should use [ScryptParams::generate_with_recommended_params] instead.
+    pub fn generate(log_n: u8, r: u32, p: u32) -> Self {
+        let mut salt = vec![0; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        Self { salt, log_n, r, p }
+    }
+
+    /// Parameters that are safe to use interactively (i.e. while the user is waiting for the
+    /// filesystem to mount), following scrypt's own recommendation for its "interactive" use
+    /// case (N=2^15, r=8, p=1).
+    pub fn generate_with_recommended_params() -> Self {
+        Self::generate(15, 8, 1)
+    }
+
+    /// Derive an [EncryptionKey] of `key_size` bytes from `password` and these parameters. The
+    /// resulting key bytes are written directly into the key's own storage and are never
+    /// exposed to the caller, and are zeroized on drop just like any other [EncryptionKey].
+    pub fn derive_key(&self, password: &str, key_size: usize) -> Result<EncryptionKey> {
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, key_size)
+            .map_err(|err| anyhow!("Invalid scrypt parameters: {:?}", err))?;
+        EncryptionKey::new(key_size, |key_data| {
+            scrypt::scrypt(password.as_bytes(), &self.salt, &params, key_data)
+                .map_err(|err| anyhow!("Scrypt key derivation failed: {:?}", err))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_sameparams_when_deriving_then_keysareidentical() {
+        let params = ScryptParams::generate(4, 2, 1);
+        let key1 = params.derive_key("correct horse battery staple", 32).unwrap();
+        let key2 = params.derive_key("correct horse battery staple", 32).unwrap();
+        assert_eq!(key1.as_bytes(), key2.as_bytes());
+    }
+
+    #[test]
+    fn given_differentpasswords_when_deriving_then_keysdiffer() {
+        let params = ScryptParams::generate(4, 2, 1);
+        let key1 = params.derive_key("password one", 32).unwrap();
+        let key2 = params.derive_key("password two", 32).unwrap();
+        assert_ne!(key1.as_bytes(), key2.as_bytes());
+    }
+
+    #[test]
+    fn given_differentsalt_when_deriving_then_keysdiffer() {
+        let params1 = ScryptParams::generate(4, 2, 1);
+        let params2 = ScryptParams::generate(4, 2, 1);
+        let key1 = params1.derive_key("correct horse battery staple", 32).unwrap();
+        let key2 = params2.derive_key("correct horse battery staple", 32).unwrap();
+        assert_ne!(key1.as_bytes(), key2.as_bytes());
+    }
+
+    #[test]
+    fn given_serializedparams_when_deserialized_then_derivessamekey() {
+        let params = ScryptParams::generate(4, 2, 1);
+        let serialized = serde_json::to_vec(&params).unwrap();
+        let deserialized: ScryptParams = serde_json::from_slice(&serialized).unwrap();
+        let key1 = params.derive_key("correct horse battery staple", 32).unwrap();
+        let key2 = deserialized
+            .derive_key("correct horse battery staple", 32)
+            .unwrap();
+        assert_eq!(key1.as_bytes(), key2.as_bytes());
+    }
+}
@@ -0,0 +1,263 @@
+use aes_gcm::{
+    aead::{generic_array::GenericArray, AeadInPlace, KeyInit},
+    AesGcm,
+};
+use aesni::{Aes128 as Aes128Ni, Aes192 as Aes192Ni, Aes256 as Aes256Ni};
+use aes_soft::{Aes128 as Aes128Soft, Aes192 as Aes192Soft, Aes256 as Aes256Soft};
+use anyhow::{anyhow, Result};
+use rand::{rngs::OsRng, RngCore};
+use typenum::U12;
+
+use super::{Cipher, EncryptionKey};
+use crate::data::Data;
+
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+// Hardware-accelerated AES (AES-NI on x86/x86_64) only exists as a distinct type on those
+// architectures. Other architectures only ever run the software implementation below.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+type Aes128GcmHw = AesGcm<Aes128Ni, U12>;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+type Aes192GcmHw = AesGcm<Aes192Ni, U12>;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+type Aes256GcmHw = AesGcm<Aes256Ni, U12>;
+type Aes128GcmSoft = AesGcm<Aes128Soft, U12>;
+type Aes192GcmSoft = AesGcm<Aes192Soft, U12>;
+type Aes256GcmSoft = AesGcm<Aes256Soft, U12>;
+
+macro_rules! impl_aesgcm_backend {
+    ($name:ident, $impl_type:ty, $key_size:expr) => {
+        pub struct $name {
+            cipher: $impl_type,
+        }
+
+        impl Cipher for $name {
+            const KEY_SIZE: usize = $key_size;
+            const CIPHERTEXT_OVERHEAD_PREFIX: usize = NONCE_SIZE;
+            const CIPHERTEXT_OVERHEAD_SUFFIX: usize = TAG_SIZE;
+
+            fn new(key: EncryptionKey) -> Result<Self> {
+                assert_eq!(Self::KEY_SIZE, key.num_bytes(), "Wrong key size");
+                let cipher = <$impl_type>::new(GenericArray::from_slice(key.as_bytes()));
+                Ok(Self { cipher })
+            }
+
+            fn encrypt_with_aad(&self, mut plaintext: Data, aad: &[u8]) -> Result<Data> {
+                let mut nonce = [0; NONCE_SIZE];
+                OsRng.fill_bytes(&mut nonce);
+
+                let tag = self
+                    .cipher
+                    .encrypt_in_place_detached(
+                        GenericArray::from_slice(&nonce),
+                        aad,
+                        plaintext.as_mut(),
+                    )
+                    .map_err(|err| anyhow!("Error encrypting data: {:?}", err))?;
+
+                plaintext.grow_region_fail_if_null_space(NONCE_SIZE, TAG_SIZE);
+                plaintext.as_mut()[..NONCE_SIZE].copy_from_slice(&nonce);
+                let suffix_start = plaintext.len() - TAG_SIZE;
+                plaintext.as_mut()[suffix_start..].copy_from_slice(&tag);
+                Ok(plaintext)
+            }
+
+            fn decrypt_with_aad(&self, mut ciphertext: Data, aad: &[u8]) -> Result<Data> {
+                if ciphertext.len() < NONCE_SIZE + TAG_SIZE {
+                    return Err(anyhow!("Ciphertext is too short to be decrypted"));
+                }
+
+                let nonce = GenericArray::clone_from_slice(&ciphertext.as_ref()[..NONCE_SIZE]);
+                let tag_offset = ciphertext.len() - TAG_SIZE;
+                let tag = GenericArray::clone_from_slice(&ciphertext.as_ref()[tag_offset..]);
+
+                ciphertext.shrink_to_subregion(NONCE_SIZE..tag_offset);
+                self.cipher
+                    .decrypt_in_place_detached(&nonce, aad, ciphertext.as_mut(), &tag)
+                    .map_err(|_| anyhow!("Error decrypting data"))?;
+
+                Ok(ciphertext)
+            }
+        }
+    };
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_aesgcm_backend!(Aes128GcmHardwareAccelerated, Aes128GcmHw, 16);
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_aesgcm_backend!(Aes192GcmHardwareAccelerated, Aes192GcmHw, 24);
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_aesgcm_backend!(Aes256GcmHardwareAccelerated, Aes256GcmHw, 32);
+impl_aesgcm_backend!(Aes128GcmSoftwareImplemented, Aes128GcmSoft, 16);
+impl_aesgcm_backend!(Aes192GcmSoftwareImplemented, Aes192GcmSoft, 24);
+impl_aesgcm_backend!(Aes256GcmSoftwareImplemented, Aes256GcmSoft, 32);
+
+/// Returns true if this CPU has the hardware features needed to run the AES-NI backed
+/// [Aes128GcmHardwareAccelerated]/[Aes256GcmHardwareAccelerated] implementations, i.e.
+/// AES-NI itself plus PCLMULQDQ (needed for the GHASH part of GCM).
+fn hardware_aes_available() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::is_x86_feature_detected!("aes") && std::is_x86_feature_detected!("pclmulqdq")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+    }
+    #[cfg(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64"
+    )))]
+    {
+        false
+    }
+}
+
+enum Impl128 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Hw(Aes128GcmHardwareAccelerated),
+    Soft(Aes128GcmSoftwareImplemented),
+}
+
+enum Impl192 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Hw(Aes192GcmHardwareAccelerated),
+    Soft(Aes192GcmSoftwareImplemented),
+}
+
+enum Impl256 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Hw(Aes256GcmHardwareAccelerated),
+    Soft(Aes256GcmSoftwareImplemented),
+}
+
+/// AES-128-GCM, dispatching to a hardware-accelerated (AES-NI) or software
+/// implementation depending on what the current CPU supports. The choice is made once,
+/// at [Cipher::new] time, so this runs at full speed without requiring a recompile for
+/// the specific machine it runs on. The hardware implementation only exists on x86/x86_64;
+/// on every other architecture, aarch64 included, the software implementation is used.
+pub struct Aes128Gcm {
+    imp: Impl128,
+}
+
+impl Cipher for Aes128Gcm {
+    const KEY_SIZE: usize = Aes128GcmSoftwareImplemented::KEY_SIZE;
+    const CIPHERTEXT_OVERHEAD_PREFIX: usize = Aes128GcmSoftwareImplemented::CIPHERTEXT_OVERHEAD_PREFIX;
+    const CIPHERTEXT_OVERHEAD_SUFFIX: usize = Aes128GcmSoftwareImplemented::CIPHERTEXT_OVERHEAD_SUFFIX;
+
+    fn new(key: EncryptionKey) -> Result<Self> {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if hardware_aes_available() {
+            return Ok(Self {
+                imp: Impl128::Hw(Aes128GcmHardwareAccelerated::new(key)?),
+            });
+        }
+        Ok(Self {
+            imp: Impl128::Soft(Aes128GcmSoftwareImplemented::new(key)?),
+        })
+    }
+
+    fn encrypt_with_aad(&self, plaintext: Data, aad: &[u8]) -> Result<Data> {
+        match &self.imp {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Impl128::Hw(cipher) => cipher.encrypt_with_aad(plaintext, aad),
+            Impl128::Soft(cipher) => cipher.encrypt_with_aad(plaintext, aad),
+        }
+    }
+
+    fn decrypt_with_aad(&self, ciphertext: Data, aad: &[u8]) -> Result<Data> {
+        match &self.imp {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Impl128::Hw(cipher) => cipher.decrypt_with_aad(ciphertext, aad),
+            Impl128::Soft(cipher) => cipher.decrypt_with_aad(ciphertext, aad),
+        }
+    }
+}
+
+/// AES-192-GCM, dispatching to a hardware-accelerated (AES-NI) or software
+/// implementation depending on what the current CPU supports. The choice is made once,
+/// at [Cipher::new] time, so this runs at full speed without requiring a recompile for
+/// the specific machine it runs on. The hardware implementation only exists on x86/x86_64;
+/// on every other architecture, aarch64 included, the software implementation is used.
+pub struct Aes192Gcm {
+    imp: Impl192,
+}
+
+impl Cipher for Aes192Gcm {
+    const KEY_SIZE: usize = Aes192GcmSoftwareImplemented::KEY_SIZE;
+    const CIPHERTEXT_OVERHEAD_PREFIX: usize = Aes192GcmSoftwareImplemented::CIPHERTEXT_OVERHEAD_PREFIX;
+    const CIPHERTEXT_OVERHEAD_SUFFIX: usize = Aes192GcmSoftwareImplemented::CIPHERTEXT_OVERHEAD_SUFFIX;
+
+    fn new(key: EncryptionKey) -> Result<Self> {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if hardware_aes_available() {
+            return Ok(Self {
+                imp: Impl192::Hw(Aes192GcmHardwareAccelerated::new(key)?),
+            });
+        }
+        Ok(Self {
+            imp: Impl192::Soft(Aes192GcmSoftwareImplemented::new(key)?),
+        })
+    }
+
+    fn encrypt_with_aad(&self, plaintext: Data, aad: &[u8]) -> Result<Data> {
+        match &self.imp {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Impl192::Hw(cipher) => cipher.encrypt_with_aad(plaintext, aad),
+            Impl192::Soft(cipher) => cipher.encrypt_with_aad(plaintext, aad),
+        }
+    }
+
+    fn decrypt_with_aad(&self, ciphertext: Data, aad: &[u8]) -> Result<Data> {
+        match &self.imp {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Impl192::Hw(cipher) => cipher.decrypt_with_aad(ciphertext, aad),
+            Impl192::Soft(cipher) => cipher.decrypt_with_aad(ciphertext, aad),
+        }
+    }
+}
+
+/// AES-256-GCM, dispatching to a hardware-accelerated (AES-NI) or software
+/// implementation depending on what the current CPU supports. The choice is made once,
+/// at [Cipher::new] time, so this runs at full speed without requiring a recompile for
+/// the specific machine it runs on. The hardware implementation only exists on x86/x86_64;
+/// on every other architecture, aarch64 included, the software implementation is used.
+pub struct Aes256Gcm {
+    imp: Impl256,
+}
+
+impl Cipher for Aes256Gcm {
+    const KEY_SIZE: usize = Aes256GcmSoftwareImplemented::KEY_SIZE;
+    const CIPHERTEXT_OVERHEAD_PREFIX: usize = Aes256GcmSoftwareImplemented::CIPHERTEXT_OVERHEAD_PREFIX;
+    const CIPHERTEXT_OVERHEAD_SUFFIX: usize = Aes256GcmSoftwareImplemented::CIPHERTEXT_OVERHEAD_SUFFIX;
+
+    fn new(key: EncryptionKey) -> Result<Self> {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if hardware_aes_available() {
+            return Ok(Self {
+                imp: Impl256::Hw(Aes256GcmHardwareAccelerated::new(key)?),
+            });
+        }
+        Ok(Self {
+            imp: Impl256::Soft(Aes256GcmSoftwareImplemented::new(key)?),
+        })
+    }
+
+    fn encrypt_with_aad(&self, plaintext: Data, aad: &[u8]) -> Result<Data> {
+        match &self.imp {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Impl256::Hw(cipher) => cipher.encrypt_with_aad(plaintext, aad),
+            Impl256::Soft(cipher) => cipher.encrypt_with_aad(plaintext, aad),
+        }
+    }
+
+    fn decrypt_with_aad(&self, ciphertext: Data, aad: &[u8]) -> Result<Data> {
+        match &self.imp {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Impl256::Hw(cipher) => cipher.decrypt_with_aad(ciphertext, aad),
+            Impl256::Soft(cipher) => cipher.decrypt_with_aad(ciphertext, aad),
+        }
+    }
+}
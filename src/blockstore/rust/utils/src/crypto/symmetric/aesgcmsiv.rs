@@ -0,0 +1,68 @@
+use aes_gcm_siv::{
+    aead::{generic_array::GenericArray, AeadInPlace, KeyInit},
+    Aes256GcmSiv as Aes256GcmSivImpl,
+};
+use anyhow::{anyhow, Result};
+use rand::{rngs::OsRng, RngCore};
+
+use super::{Cipher, EncryptionKey};
+use crate::data::Data;
+
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+/// AES-256 in the nonce-misuse-resistant SIV mode (RFC 8452): a per-message synthetic IV is
+/// derived (via POLYVAL) from the master key, nonce, AAD and plaintext, and that IV is both
+/// the authentication tag and the key for the AES-CTR keystream that encrypts the plaintext.
+/// Unlike plain AES-GCM, accidentally reusing a nonce here does not reveal the key or the
+/// plaintext of either message - worst case it reveals whether two (plaintext, AAD) pairs were
+/// identical. This makes it the safer default whenever nonce uniqueness can't be guaranteed
+/// (e.g. restoring an old snapshot of the key material).
+pub struct Aes256GcmSiv {
+    cipher: Aes256GcmSivImpl,
+}
+
+impl Cipher for Aes256GcmSiv {
+    const KEY_SIZE: usize = 32;
+    const CIPHERTEXT_OVERHEAD_PREFIX: usize = NONCE_SIZE;
+    const CIPHERTEXT_OVERHEAD_SUFFIX: usize = TAG_SIZE;
+
+    fn new(key: EncryptionKey) -> Result<Self> {
+        assert_eq!(Self::KEY_SIZE, key.num_bytes(), "Wrong key size");
+        let cipher = Aes256GcmSivImpl::new(GenericArray::from_slice(key.as_bytes()));
+        Ok(Self { cipher })
+    }
+
+    fn encrypt_with_aad(&self, mut plaintext: Data, aad: &[u8]) -> Result<Data> {
+        let mut nonce = [0; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(GenericArray::from_slice(&nonce), aad, plaintext.as_mut())
+            .map_err(|err| anyhow!("Error encrypting data: {:?}", err))?;
+
+        plaintext.grow_region_fail_if_null_space(NONCE_SIZE, TAG_SIZE);
+        plaintext.as_mut()[..NONCE_SIZE].copy_from_slice(&nonce);
+        let suffix_start = plaintext.len() - TAG_SIZE;
+        plaintext.as_mut()[suffix_start..].copy_from_slice(&tag);
+        Ok(plaintext)
+    }
+
+    fn decrypt_with_aad(&self, mut ciphertext: Data, aad: &[u8]) -> Result<Data> {
+        if ciphertext.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(anyhow!("Ciphertext is too short to be decrypted"));
+        }
+
+        let nonce = GenericArray::clone_from_slice(&ciphertext.as_ref()[..NONCE_SIZE]);
+        let tag_offset = ciphertext.len() - TAG_SIZE;
+        let tag = GenericArray::clone_from_slice(&ciphertext.as_ref()[tag_offset..]);
+
+        ciphertext.shrink_to_subregion(NONCE_SIZE..tag_offset);
+        self.cipher
+            .decrypt_in_place_detached(&nonce, aad, ciphertext.as_mut(), &tag)
+            .map_err(|_| anyhow!("Error decrypting data"))?;
+
+        Ok(ciphertext)
+    }
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+
+use super::EncryptionKey;
+use crate::data::Data;
+
+/// A symmetric cipher that can encrypt and decrypt [Data] blocks using a key of a fixed size.
+///
+/// Implementations are expected to prepend/append any nonce, tag or other metadata they need
+/// to [Data] themselves, and to report the exact number of bytes they add via
+/// [Cipher::CIPHERTEXT_OVERHEAD_PREFIX] and [Cipher::CIPHERTEXT_OVERHEAD_SUFFIX] so that callers
+/// can preallocate the right amount of space.
+pub trait Cipher: Sized + Send + Sync + 'static {
+    /// Size of the encryption key, in bytes.
+    const KEY_SIZE: usize;
+
+    /// Number of bytes added to the front of the ciphertext, e.g. for a nonce.
+    const CIPHERTEXT_OVERHEAD_PREFIX: usize;
+
+    /// Number of bytes added to the back of the ciphertext, e.g. for an authentication tag.
+    const CIPHERTEXT_OVERHEAD_SUFFIX: usize;
+
+    fn new(key: EncryptionKey) -> Result<Self>;
+
+    /// Encrypt `plaintext` in place, i.e. `plaintext` must already have
+    /// [Cipher::CIPHERTEXT_OVERHEAD_PREFIX] bytes of space before and
+    /// [Cipher::CIPHERTEXT_OVERHEAD_SUFFIX] bytes of space after its actual content,
+    /// see [crate::data::Data::shrink_to_subregion]/[crate::data::Data::grow_region].
+    fn encrypt(&self, plaintext: Data) -> Result<Data> {
+        self.encrypt_with_aad(plaintext, &[])
+    }
+
+    fn decrypt(&self, ciphertext: Data) -> Result<Data> {
+        self.decrypt_with_aad(ciphertext, &[])
+    }
+
+    /// Like [Cipher::encrypt], but also authenticates `aad` as part of the authentication tag,
+    /// without encrypting it. Decrypting with [Cipher::decrypt_with_aad] will fail unless it is
+    /// given the exact same `aad` that was used here. This allows binding context (e.g. a block
+    /// id) into the ciphertext so it cannot be silently moved to a different context.
+    fn encrypt_with_aad(&self, plaintext: Data, aad: &[u8]) -> Result<Data>;
+
+    /// Counterpart to [Cipher::encrypt_with_aad]. Fails if `aad` doesn't match what was passed
+    /// to [Cipher::encrypt_with_aad] when the ciphertext was created.
+    fn decrypt_with_aad(&self, ciphertext: Data, aad: &[u8]) -> Result<Data>;
+}
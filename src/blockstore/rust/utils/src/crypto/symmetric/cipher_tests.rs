@@ -6,10 +6,10 @@ use rand::{rngs::StdRng, RngCore, SeedableRng};
 use lockable::InfallibleUnwrap;
 
 use super::aesgcm::{
-    Aes128Gcm, Aes256Gcm, Aes256GcmHardwareAccelerated, Aes256GcmSoftwareImplemented,
+    Aes128Gcm, Aes192Gcm, Aes256Gcm, Aes256GcmHardwareAccelerated, Aes256GcmSoftwareImplemented,
 };
 use super::XChaCha20Poly1305;
-use super::{Cipher, EncryptionKey};
+use super::{Aes256GcmSiv, Cipher, CipherAlgorithm, EncryptionKey, FramedCipher, MultiCipher};
 use crate::data::Data;
 
 pub fn key(num_bytes: usize, seed: u64) -> EncryptionKey {
@@ -98,6 +98,9 @@ mod enc_dec {
     #[instantiate_tests(<Aes128Gcm, Aes128Gcm>)]
     mod aes128gcm {}
 
+    #[instantiate_tests(<Aes192Gcm, Aes192Gcm>)]
+    mod aes192gcm {}
+
     #[instantiate_tests(<Aes256GcmSoftwareImplemented, Aes256GcmSoftwareImplemented>)]
     mod aes256gcm_software {}
 
@@ -108,6 +111,9 @@ mod enc_dec {
     #[instantiate_tests(<Aes256Gcm, Aes256Gcm>)]
     mod aes256gcm {}
 
+    #[instantiate_tests(<Aes256GcmSiv, Aes256GcmSiv>)]
+    mod aes256gcmsiv {}
+
     // Test interoperability (i.e. encrypting with one and decrypting with the other works)
     #[instantiate_tests(<Aes256GcmHardwareAccelerated, Aes256GcmSoftwareImplemented>)]
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] // TODO Better aes-ni feature detection
@@ -182,6 +188,9 @@ mod basics {
     #[instantiate_tests(<Aes128Gcm>)]
     mod aes128gcm {}
 
+    #[instantiate_tests(<Aes192Gcm>)]
+    mod aes192gcm {}
+
     #[instantiate_tests(<Aes256GcmSoftwareImplemented>)]
     mod aes256gcm_software {}
 
@@ -191,6 +200,63 @@ mod basics {
 
     #[instantiate_tests(<Aes256Gcm>)]
     mod aes256gcm {}
+
+    #[instantiate_tests(<Aes256GcmSiv>)]
+    mod aes256gcmsiv {}
+}
+
+mod framed {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn given_ciphertext_when_decryptingwithsamecipher_then_succeeds() {
+        let cipher = FramedCipher::<XChaCha20Poly1305>::new(key(XChaCha20Poly1305::KEY_SIZE, 1))
+            .unwrap();
+        let plaintext = allocate_space_for_ciphertext::<XChaCha20Poly1305>(b"Hello World");
+        let ciphertext = cipher.encrypt(plaintext.clone().into()).unwrap();
+        let decrypted = cipher.decrypt(ciphertext.into()).unwrap();
+        assert_eq!(plaintext.as_ref(), decrypted.as_ref());
+    }
+
+    #[test]
+    fn given_ciphertext_when_decryptingwithdifferentalgorithm_then_fails() {
+        let xchacha = FramedCipher::<XChaCha20Poly1305>::new(key(XChaCha20Poly1305::KEY_SIZE, 1))
+            .unwrap();
+        let aes = FramedCipher::<Aes256Gcm>::new(key(Aes256Gcm::KEY_SIZE, 1)).unwrap();
+        let plaintext = allocate_space_for_ciphertext::<XChaCha20Poly1305>(b"Hello World");
+        let ciphertext = xchacha.encrypt(plaintext.into()).unwrap();
+        assert!(aes.decrypt(ciphertext.into()).is_err());
+    }
+
+    #[test]
+    fn given_headers_for_each_algorithm_when_decryptingviamulticipher_then_routescorrectly() {
+        let xchacha = FramedCipher::<XChaCha20Poly1305>::new(key(XChaCha20Poly1305::KEY_SIZE, 1))
+            .unwrap();
+        let aes = FramedCipher::<Aes256Gcm>::new(key(Aes256Gcm::KEY_SIZE, 2)).unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(
+            CipherAlgorithm::XChaCha20Poly1305,
+            key(XChaCha20Poly1305::KEY_SIZE, 1),
+        );
+        keys.insert(CipherAlgorithm::Aes256Gcm, key(Aes256Gcm::KEY_SIZE, 2));
+        let multi = MultiCipher::new(keys).unwrap();
+
+        let plaintext1 = allocate_space_for_ciphertext::<XChaCha20Poly1305>(b"via xchacha");
+        let ciphertext1 = xchacha.encrypt(plaintext1.clone().into()).unwrap();
+        assert_eq!(
+            plaintext1.as_ref(),
+            multi.decrypt_any(ciphertext1.into()).unwrap().as_ref()
+        );
+
+        let plaintext2 = allocate_space_for_ciphertext::<Aes256Gcm>(b"via aes");
+        let ciphertext2 = aes.encrypt(plaintext2.clone().into()).unwrap();
+        assert_eq!(
+            plaintext2.as_ref(),
+            multi.decrypt_any(ciphertext2.into()).unwrap().as_ref()
+        );
+    }
 }
 
 mod xchacha20poly1305 {
@@ -226,6 +292,84 @@ mod aes_128_gcm {
     }
 }
 
+mod aes_192_gcm {
+    use super::*;
+
+    #[test]
+    fn test_backward_compatibility() {
+        // Test a preencrypted message to make sure we can still decrypt it
+        let cipher = Aes192Gcm::new(key(Aes192Gcm::KEY_SIZE, 1)).unwrap();
+        let ciphertext = hex::decode(
+            "000102030405060708090a0b24b0267535868855a90d28b4b87e91b389a6849f02661d49a9cc97",
+        )
+        .unwrap();
+        assert_eq!(
+            b"Hello World",
+            &cipher.decrypt(ciphertext.into()).unwrap().as_ref()
+        );
+    }
+}
+
+#[generic_tests::define]
+mod aad {
+    use super::*;
+
+    #[test]
+    fn given_correctaad_when_decrypting_then_succeeds<C: Cipher>() {
+        let cipher = C::new(key(C::KEY_SIZE, 1)).unwrap();
+        let plaintext = allocate_space_for_ciphertext::<C>(b"Hello World");
+        let ciphertext = cipher
+            .encrypt_with_aad(plaintext.clone().into(), b"some aad")
+            .unwrap();
+        let decrypted = cipher
+            .decrypt_with_aad(ciphertext.into(), b"some aad")
+            .unwrap();
+        assert_eq!(plaintext.as_ref(), decrypted.as_ref());
+    }
+
+    #[test]
+    fn given_wrongaad_when_decrypting_then_fails<C: Cipher>() {
+        let cipher = C::new(key(C::KEY_SIZE, 1)).unwrap();
+        let plaintext = allocate_space_for_ciphertext::<C>(b"Hello World");
+        let ciphertext = cipher
+            .encrypt_with_aad(plaintext.into(), b"some aad")
+            .unwrap();
+        let decrypted = cipher.decrypt_with_aad(ciphertext.into(), b"different aad");
+        assert!(decrypted.is_err());
+    }
+
+    #[test]
+    fn given_noaad_when_decryptingwithaad_then_fails<C: Cipher>() {
+        let cipher = C::new(key(C::KEY_SIZE, 1)).unwrap();
+        let plaintext = allocate_space_for_ciphertext::<C>(b"Hello World");
+        let ciphertext = cipher.encrypt(plaintext.into()).unwrap();
+        let decrypted = cipher.decrypt_with_aad(ciphertext.into(), b"some aad");
+        assert!(decrypted.is_err());
+    }
+
+    #[instantiate_tests(<XChaCha20Poly1305>)]
+    mod xchacha20poly1305 {}
+
+    #[instantiate_tests(<Aes128Gcm>)]
+    mod aes128gcm {}
+
+    #[instantiate_tests(<Aes192Gcm>)]
+    mod aes192gcm {}
+
+    #[instantiate_tests(<Aes256GcmSoftwareImplemented>)]
+    mod aes256gcm_software {}
+
+    #[instantiate_tests(<Aes256GcmHardwareAccelerated>)]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] // TODO Better aes-ni feature detection
+    mod aes256gcm_hardware {}
+
+    #[instantiate_tests(<Aes256Gcm>)]
+    mod aes256gcm {}
+
+    #[instantiate_tests(<Aes256GcmSiv>)]
+    mod aes256gcmsiv {}
+}
+
 mod aes_256_gcm {
     use super::*;
 
@@ -0,0 +1,151 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+use super::aesgcm::{Aes128Gcm, Aes256Gcm};
+use super::{Cipher, EncryptionKey, XChaCha20Poly1305};
+use crate::data::Data;
+
+/// A single byte identifying which [Cipher] was used to produce a ciphertext, prepended to
+/// [Cipher::CIPHERTEXT_OVERHEAD_PREFIX] and authenticated as AAD. This is what allows us to migrate a
+/// filesystem from one cipher to another without a flag day: old blocks keep decrypting
+/// correctly via their own header while new blocks are written with the new one.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CipherAlgorithm {
+    XChaCha20Poly1305 = 0,
+    Aes128Gcm = 1,
+    Aes256Gcm = 2,
+}
+
+/// Size, in bytes, of the header prepended by [FramedCipher].
+pub const HEADER_SIZE: usize = 1;
+
+impl CipherAlgorithm {
+    fn from_header_byte(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Self::XChaCha20Poly1305),
+            1 => Ok(Self::Aes128Gcm),
+            2 => Ok(Self::Aes256Gcm),
+            _ => bail!("Unknown cipher algorithm identifier: {}", byte),
+        }
+    }
+
+    fn header_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+/// A [Cipher] that additionally knows which [CipherAlgorithm] it corresponds to, so it can be
+/// wrapped by [FramedCipher] and recognized by [MultiCipher::decrypt_any].
+pub trait IdentifiedCipher: Cipher {
+    const ALGORITHM: CipherAlgorithm;
+}
+
+impl IdentifiedCipher for XChaCha20Poly1305 {
+    const ALGORITHM: CipherAlgorithm = CipherAlgorithm::XChaCha20Poly1305;
+}
+impl IdentifiedCipher for Aes128Gcm {
+    const ALGORITHM: CipherAlgorithm = CipherAlgorithm::Aes128Gcm;
+}
+impl IdentifiedCipher for Aes256Gcm {
+    const ALGORITHM: CipherAlgorithm = CipherAlgorithm::Aes256Gcm;
+}
+
+/// Wraps a [Cipher] with a self-describing header: every ciphertext it produces starts with
+/// one byte identifying [C::ALGORITHM], authenticated as AAD so it cannot be tampered with to
+/// make decryption interpret the ciphertext using a different algorithm.
+pub struct FramedCipher<C: IdentifiedCipher> {
+    cipher: C,
+}
+
+impl<C: IdentifiedCipher> FramedCipher<C> {
+    pub fn new(key: EncryptionKey) -> Result<Self> {
+        Ok(Self {
+            cipher: C::new(key)?,
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: Data) -> Result<Data> {
+        let header = [C::ALGORITHM.header_byte()];
+        // Callers are expected to have reserved C::CIPHERTEXT_OVERHEAD_PREFIX/SUFFIX around the
+        // plaintext already, as with any other [Cipher]; our header comes on top of that.
+        let ciphertext = self.cipher.encrypt_with_aad(plaintext, &header)?;
+
+        let mut result = Data::from(vec![0; HEADER_SIZE + ciphertext.len()]);
+        result.as_mut()[0] = C::ALGORITHM.header_byte();
+        result.as_mut()[HEADER_SIZE..].copy_from_slice(ciphertext.as_ref());
+        Ok(result)
+    }
+
+    pub fn decrypt(&self, ciphertext: Data) -> Result<Data> {
+        if ciphertext.len() < HEADER_SIZE {
+            bail!("Ciphertext is too short to contain a cipher-algorithm header");
+        }
+        let header_byte = ciphertext.as_ref()[0];
+        let algorithm = CipherAlgorithm::from_header_byte(header_byte)?;
+        if algorithm != C::ALGORITHM {
+            bail!(
+                "Ciphertext was encrypted with {:?} but we're trying to decrypt it with {:?}",
+                algorithm,
+                C::ALGORITHM,
+            );
+        }
+        let mut ciphertext = ciphertext;
+        ciphertext.shrink_to_subregion(HEADER_SIZE..ciphertext.len());
+        self.cipher.decrypt_with_aad(ciphertext, &[header_byte])
+    }
+}
+
+/// Holds keys for multiple algorithms at once and dispatches decryption to the right one based
+/// on the self-describing header written by [FramedCipher]. This is the tool that enables a
+/// lazy rekey/migration: write new blocks with the new cipher while still being able to read
+/// blocks that were never touched since the migration started.
+pub struct MultiCipher {
+    xchacha20poly1305: Option<FramedCipher<XChaCha20Poly1305>>,
+    aes128gcm: Option<FramedCipher<Aes128Gcm>>,
+    aes256gcm: Option<FramedCipher<Aes256Gcm>>,
+}
+
+impl MultiCipher {
+    pub fn new(keys: HashMap<CipherAlgorithm, EncryptionKey>) -> Result<Self> {
+        let mut keys = keys;
+        Ok(Self {
+            xchacha20poly1305: keys
+                .remove(&CipherAlgorithm::XChaCha20Poly1305)
+                .map(FramedCipher::new)
+                .transpose()?,
+            aes128gcm: keys
+                .remove(&CipherAlgorithm::Aes128Gcm)
+                .map(FramedCipher::new)
+                .transpose()?,
+            aes256gcm: keys
+                .remove(&CipherAlgorithm::Aes256Gcm)
+                .map(FramedCipher::new)
+                .transpose()?,
+        })
+    }
+
+    pub fn decrypt_any(&self, ciphertext: Data) -> Result<Data> {
+        if ciphertext.len() < HEADER_SIZE {
+            bail!("Ciphertext is too short to contain a cipher-algorithm header");
+        }
+        let algorithm = CipherAlgorithm::from_header_byte(ciphertext.as_ref()[0])?;
+        match algorithm {
+            CipherAlgorithm::XChaCha20Poly1305 => self
+                .xchacha20poly1305
+                .as_ref()
+                .ok_or_else(|| anyhow!("No key configured for {:?}", algorithm))?
+                .decrypt(ciphertext),
+            CipherAlgorithm::Aes128Gcm => self
+                .aes128gcm
+                .as_ref()
+                .ok_or_else(|| anyhow!("No key configured for {:?}", algorithm))?
+                .decrypt(ciphertext),
+            CipherAlgorithm::Aes256Gcm => self
+                .aes256gcm
+                .as_ref()
+                .ok_or_else(|| anyhow!("No key configured for {:?}", algorithm))?
+                .decrypt(ciphertext),
+        }
+    }
+}
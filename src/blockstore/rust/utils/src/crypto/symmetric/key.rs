@@ -0,0 +1,51 @@
+use std::fmt::{self, Debug};
+
+/// An encryption key of a given size. The key bytes are stored pinned on the heap and are
+/// zeroed out on drop so that key material doesn't linger around in memory or swap longer
+/// than necessary.
+///
+/// Because key material should never be copied around more than necessary, [EncryptionKey::new]
+/// takes a closure that fills the already-allocated key buffer in place instead of having
+/// callers build a `Vec<u8>` themselves and hand it over.
+pub struct EncryptionKey {
+    key: Box<[u8]>,
+}
+
+impl EncryptionKey {
+    pub fn new<E>(
+        num_bytes: usize,
+        init_key_data: impl FnOnce(&mut [u8]) -> Result<(), E>,
+    ) -> Result<Self, E> {
+        let mut key = vec![0; num_bytes].into_boxed_slice();
+        init_key_data(&mut key)?;
+        Ok(Self { key })
+    }
+
+    #[inline]
+    pub fn num_bytes(&self) -> usize {
+        self.key.len()
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        // Don't leave key material lying around in memory longer than necessary.
+        for byte in self.key.iter_mut() {
+            *byte = 0;
+        }
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the actual key material.
+        f.debug_struct("EncryptionKey")
+            .field("num_bytes", &self.num_bytes())
+            .finish()
+    }
+}
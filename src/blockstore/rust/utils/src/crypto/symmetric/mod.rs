@@ -0,0 +1,15 @@
+pub mod aesgcm;
+mod aesgcmsiv;
+mod cipher;
+mod framed;
+mod key;
+mod xchacha20poly1305;
+
+#[cfg(test)]
+mod cipher_tests;
+
+pub use aesgcmsiv::Aes256GcmSiv;
+pub use cipher::Cipher;
+pub use framed::{CipherAlgorithm, FramedCipher, IdentifiedCipher, MultiCipher};
+pub use key::EncryptionKey;
+pub use xchacha20poly1305::XChaCha20Poly1305;
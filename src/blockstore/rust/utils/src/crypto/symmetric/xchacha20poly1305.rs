@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, rand_core::RngCore, OsRng},
+    AeadInPlace, KeyInit, XChaCha20Poly1305 as ChaChaImpl, XNonce,
+};
+
+use super::{Cipher, EncryptionKey};
+use crate::data::Data;
+
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+
+pub struct XChaCha20Poly1305 {
+    cipher: ChaChaImpl,
+}
+
+impl Cipher for XChaCha20Poly1305 {
+    const KEY_SIZE: usize = 32;
+    const CIPHERTEXT_OVERHEAD_PREFIX: usize = NONCE_SIZE;
+    const CIPHERTEXT_OVERHEAD_SUFFIX: usize = TAG_SIZE;
+
+    fn new(key: EncryptionKey) -> Result<Self> {
+        assert_eq!(Self::KEY_SIZE, key.num_bytes(), "Wrong key size");
+        let cipher = ChaChaImpl::new(GenericArray::from_slice(key.as_bytes()));
+        Ok(Self { cipher })
+    }
+
+    fn encrypt_with_aad(&self, mut plaintext: Data, aad: &[u8]) -> Result<Data> {
+        let mut nonce = [0; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(XNonce::from_slice(&nonce), aad, plaintext.as_mut())
+            .map_err(|err| anyhow!("Error encrypting data: {:?}", err))?;
+
+        plaintext.grow_region_fail_if_null_space(NONCE_SIZE, TAG_SIZE);
+        plaintext.as_mut()[..NONCE_SIZE].copy_from_slice(&nonce);
+        plaintext.as_mut()[(plaintext.len() - TAG_SIZE)..].copy_from_slice(&tag);
+        Ok(plaintext)
+    }
+
+    fn decrypt_with_aad(&self, mut ciphertext: Data, aad: &[u8]) -> Result<Data> {
+        if ciphertext.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(anyhow!("Ciphertext is too short to be decrypted"));
+        }
+
+        let nonce = XNonce::clone_from_slice(&ciphertext.as_ref()[..NONCE_SIZE]);
+        let tag_offset = ciphertext.len() - TAG_SIZE;
+        let tag = GenericArray::clone_from_slice(&ciphertext.as_ref()[tag_offset..]);
+
+        ciphertext.shrink_to_subregion(NONCE_SIZE..tag_offset);
+        self.cipher
+            .decrypt_in_place_detached(&nonce, aad, ciphertext.as_mut(), &tag)
+            .map_err(|_| anyhow!("Error decrypting data"))?;
+
+        Ok(ciphertext)
+    }
+}